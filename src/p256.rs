@@ -0,0 +1,206 @@
+//! The `age1yubikey1...` bech32 recipient encoding for a YubiKey PIV
+//! P-256 key.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
+
+use bech32::{FromBase32, ToBase32, Variant};
+
+use crate::format::{StanzaCurve, TAG_BYTES};
+
+/// The bech32 human-readable part for a P-256 PIV recipient, giving the
+/// `age1yubikey1...` encoding.
+const RECIPIENT_HRP: &str = "age1yubikey";
+
+/// A YubiKey PIV recipient on the P-256 curve.
+///
+/// Displays as (and parses from) the canonical `age1yubikey1...` bech32
+/// encoding, so a recipient printed to a `recipients.txt`-style file and
+/// read back compares equal: `Recipient::from_str(&r.to_string()) ==
+/// Ok(r)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recipient {
+    tag: [u8; TAG_BYTES],
+    pubkey: p256::PublicKey,
+}
+
+impl Recipient {
+    /// Builds a recipient from a YubiKey's recipient tag and the public
+    /// key of its PIV slot.
+    pub fn from_tag_and_pubkey(tag: [u8; TAG_BYTES], pubkey: p256::PublicKey) -> Self {
+        Recipient { tag, pubkey }
+    }
+
+    /// The YubiKey PIV public key file keys are wrapped to.
+    pub fn public_key(&self) -> &p256::PublicKey {
+        &self.pubkey
+    }
+
+    /// The 4-byte tag used to pick out the matching stanza during
+    /// decryption.
+    pub fn tag(&self) -> [u8; TAG_BYTES] {
+        self.tag
+    }
+}
+
+/// An error returned by [`Recipient`]'s [`FromStr`] implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseRecipientError {
+    /// The string is not valid bech32.
+    Bech32,
+    /// The string is valid bech32, but not for the `age1yubikey` HRP, or
+    /// it uses the Bech32m variant instead of Bech32.
+    Hrp,
+    /// The decoded payload isn't `tag || compressed point` length.
+    Length,
+    /// The decoded payload isn't a valid P-256 point.
+    Point,
+}
+
+impl fmt::Display for ParseRecipientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseRecipientError::Bech32 => write!(f, "invalid bech32 string"),
+            ParseRecipientError::Hrp => write!(f, "not an age1yubikey recipient"),
+            ParseRecipientError::Length => write!(f, "invalid recipient payload length"),
+            ParseRecipientError::Point => write!(f, "invalid P-256 point"),
+        }
+    }
+}
+
+impl std::error::Error for ParseRecipientError {}
+
+impl FromStr for Recipient {
+    type Err = ParseRecipientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (hrp, data, variant) = bech32::decode(s).map_err(|_| ParseRecipientError::Bech32)?;
+        if hrp != RECIPIENT_HRP || variant != Variant::Bech32 {
+            return Err(ParseRecipientError::Hrp);
+        }
+
+        let bytes = Vec::<u8>::from_base32(&data).map_err(|_| ParseRecipientError::Bech32)?;
+        if bytes.len() != TAG_BYTES + crate::format::P256::EPK_BYTES {
+            return Err(ParseRecipientError::Length);
+        }
+
+        let (tag, pubkey_bytes) = bytes.split_at(TAG_BYTES);
+        let pubkey =
+            crate::format::P256::decode_point(pubkey_bytes).ok_or(ParseRecipientError::Point)?;
+
+        Ok(Recipient {
+            tag: tag.try_into().expect("split_at(TAG_BYTES) yields TAG_BYTES bytes"),
+            pubkey,
+        })
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = Vec::with_capacity(TAG_BYTES + crate::format::P256::EPK_BYTES);
+        bytes.extend_from_slice(&self.tag);
+        bytes.extend_from_slice(&crate::format::P256::encode_point(&self.pubkey));
+
+        let encoded = bech32::encode(RECIPIENT_HRP, bytes.to_base32(), Variant::Bech32)
+            .expect("RECIPIENT_HRP is a valid bech32 human-readable part");
+        f.write_str(&encoded)
+    }
+}
+
+/// `Recipient` (de)serializes as its canonical `age1yubikey1...` bech32
+/// string for human-readable formats, and as a compact `(tag, compressed
+/// point)` byte tuple for binary ones -- matching the split
+/// [`crate::format::RecipientLine`] uses, and reusing this module's own
+/// `FromStr`/`Display` and [`crate::format::P256`] point validation so
+/// invalid recipients are rejected on the way in either way.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Recipient {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.serialize_str(&self.to_string())
+        } else {
+            use serde::ser::SerializeTuple;
+
+            let pubkey_bytes = crate::format::P256::encode_point(&self.pubkey);
+
+            let mut tup = s.serialize_tuple(2)?;
+            tup.serialize_element(&self.tag)?;
+            tup.serialize_element(&pubkey_bytes)?;
+            tup.end()
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Recipient {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct RecipientVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for RecipientVisitor {
+            type Value = Recipient;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an age1yubikey1... recipient, or a (tag, compressed point) byte tuple")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Recipient, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            // Mirrors RecipientLine's visit_seq: bincode and other
+            // non-self-describing formats deserialize tuples positionally.
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Recipient, A::Error> {
+                let tag: [u8; TAG_BYTES] = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                let pubkey_bytes: Vec<u8> = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
+                let pubkey = crate::format::P256::decode_point(&pubkey_bytes)
+                    .ok_or_else(|| serde::de::Error::custom("invalid P-256 point"))?;
+                Ok(Recipient { tag, pubkey })
+            }
+        }
+
+        if d.is_human_readable() {
+            d.deserialize_str(RecipientVisitor)
+        } else {
+            d.deserialize_tuple(2, RecipientVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn recipient_roundtrips_through_bech32_string() {
+        let pubkey = p256::SecretKey::random(OsRng).public_key();
+        let recipient = Recipient::from_tag_and_pubkey([1, 2, 3, 4], pubkey);
+
+        let encoded = recipient.to_string();
+        assert!(encoded.starts_with("age1yubikey1"));
+
+        let parsed: Recipient = encoded.parse().expect("recipient round-trips through bech32");
+        assert_eq!(parsed, recipient);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn recipient_roundtrips_through_bincode() {
+        let pubkey = p256::SecretKey::random(OsRng).public_key();
+        let recipient = Recipient::from_tag_and_pubkey([5, 6, 7, 8], pubkey);
+
+        let encoded = bincode::serialize(&recipient).expect("Recipient serializes to bincode");
+        let decoded: Recipient =
+            bincode::deserialize(&encoded).expect("Recipient deserializes from bincode");
+
+        assert_eq!(decoded, recipient);
+    }
+}